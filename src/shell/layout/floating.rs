@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::shell::{Element, Workspace};
+use smithay::{
+    utils::{Logical, Point, Size},
+    wayland::shell::xdg::ResizeEdge,
+};
+
+/// Free-form placement of windows, the default layout for a workspace that
+/// has not opted into tiling.
+#[derive(Debug, Default)]
+pub struct FloatingLayout;
+
+impl FloatingLayout {
+    pub fn map_window(&mut self, workspace: &mut Workspace, window: &Element, location: Point<i32, Logical>) {
+        workspace.space.map_element(window.clone(), location, true);
+    }
+
+    pub fn unmap_window(&mut self, workspace: &mut Workspace, window: &Element) {
+        workspace.space.unmap_elem(window);
+    }
+}
+
+/// Active pointer-driven resize of a floating window, started in response to
+/// an `xdg_toplevel::resize` request and driven to completion only once the
+/// client has acknowledged and commited the matching size.
+#[derive(Debug, Clone)]
+pub struct ResizeSurfaceGrab {
+    pub window: Element,
+    pub edges: ResizeEdge,
+    pub initial_window_location: Point<i32, Logical>,
+    pub initial_window_size: Size<i32, Logical>,
+    pub last_window_size: Size<i32, Logical>,
+}
+
+impl ResizeSurfaceGrab {
+    /// Applies the size the client just committed to the window's location,
+    /// so that resizing from the top/left edges does not make the window
+    /// appear to slide out from underneath the pointer.
+    pub fn apply_resize_to_location(window: Element, workspace: &mut Workspace) {
+        let Some(new_size) = window.toplevel().current_state().size else {
+            return;
+        };
+
+        if let Some(location) = workspace.space.element_location(&window) {
+            let mut new_location = location;
+
+            if let Some(state) = window.user_data().get::<std::cell::RefCell<ResizeState>>() {
+                let state = state.borrow();
+                if state.edges.contains(ResizeEdge::LEFT) {
+                    new_location.x = state.initial_window_location.x
+                        + (state.initial_window_size.w - new_size.w);
+                }
+                if state.edges.contains(ResizeEdge::TOP) {
+                    new_location.y = state.initial_window_location.y
+                        + (state.initial_window_size.h - new_size.h);
+                }
+            }
+
+            if new_location != location {
+                workspace.space.map_element(window, new_location, false);
+            }
+        }
+    }
+}
+
+/// Bookkeeping stashed on the window being resized so that later commits can
+/// keep re-deriving the correct top/left-anchored location.
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeState {
+    pub edges: ResizeEdge,
+    pub initial_window_location: Point<i32, Logical>,
+    pub initial_window_size: Size<i32, Logical>,
+}
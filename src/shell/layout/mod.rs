@@ -0,0 +1,4 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+pub mod floating;
+pub mod scrolling;
@@ -0,0 +1,434 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use crate::shell::{Element, Layout, Workspace, WorkspaceHandle};
+use smithay::{
+    desktop::{space::SpaceElement, Kind, Space},
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+    utils::{Logical, Point, Size},
+    wayland::shell::xdg::ResizeEdge,
+};
+
+/// Preset fractions of the output width a [`Column`] can occupy. Cycled by a
+/// keybind rather than freely resized, mirroring how most scrollable-tiling
+/// window managers expose column width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidthMode {
+    OneThird,
+    Half,
+    TwoThirds,
+}
+
+impl WidthMode {
+    fn fraction(self) -> f64 {
+        match self {
+            WidthMode::OneThird => 1.0 / 3.0,
+            WidthMode::Half => 1.0 / 2.0,
+            WidthMode::TwoThirds => 2.0 / 3.0,
+        }
+    }
+
+    /// Cycles to the next wider preset, wrapping back around to the
+    /// narrowest one.
+    fn next(self) -> Self {
+        match self {
+            WidthMode::OneThird => WidthMode::Half,
+            WidthMode::Half => WidthMode::TwoThirds,
+            WidthMode::TwoThirds => WidthMode::OneThird,
+        }
+    }
+
+    /// The inverse of [`WidthMode::next`], used when a resize-grab drag goes
+    /// narrower instead of wider.
+    fn prev(self) -> Self {
+        match self {
+            WidthMode::OneThird => WidthMode::TwoThirds,
+            WidthMode::Half => WidthMode::OneThird,
+            WidthMode::TwoThirds => WidthMode::Half,
+        }
+    }
+}
+
+/// A single column on the horizontal strip. Always spans the full usable
+/// output height, split among its stacked windows by `ratios`.
+#[derive(Debug)]
+pub struct Column {
+    pub windows: Vec<Element>,
+    pub width: WidthMode,
+    /// Share of the column's height each window in `windows` gets, same
+    /// length as `windows` and always summing to `1.0`. Starts out even.
+    ratios: Vec<f32>,
+}
+
+/// Drag distance, in logical pixels along the height axis, that moves a
+/// window's share of its column by a full `1.0`; shorter drags move it
+/// proportionally less (clamped so neither window drops below `0.05`).
+const HEIGHT_DRAG_STEP: f32 = 400.0;
+
+impl Column {
+    fn new(window: Element) -> Self {
+        Column {
+            windows: vec![window],
+            width: WidthMode::Half,
+            ratios: vec![1.0],
+        }
+    }
+
+    fn remove(&mut self, window: &Element) {
+        if let Some(idx) = self.windows.iter().position(|w| w == window) {
+            self.windows.remove(idx);
+            self.ratios.remove(idx);
+            self.rebalance();
+        }
+    }
+
+    /// Resets every window in the column back to an even share of its
+    /// height. Called whenever the column's membership changes, so manual
+    /// height nudges don't linger against a now-unrelated set of windows.
+    fn rebalance(&mut self) {
+        let count = self.windows.len().max(1);
+        self.ratios = vec![1.0 / count as f32; count];
+    }
+
+    /// Grows `window_idx`'s share of the column's height by `delta_ratio`,
+    /// taking it out of the neighbour on the side of `edge`: the window
+    /// above for a `TOP`-edge drag, the window below for a `BOTTOM`-edge one
+    /// (falling back to whichever neighbour exists at the ends of the
+    /// column).
+    fn nudge_ratio(&mut self, window_idx: usize, edge: ResizeEdge, delta_ratio: f32) {
+        if self.ratios.len() < 2 {
+            return;
+        }
+        let neighbour = if edge.contains(ResizeEdge::TOP) && window_idx > 0 {
+            window_idx - 1
+        } else if window_idx + 1 < self.ratios.len() {
+            window_idx + 1
+        } else {
+            window_idx - 1
+        };
+        let delta = delta_ratio.clamp(-self.ratios[window_idx] + 0.05, self.ratios[neighbour] - 0.05);
+        self.ratios[window_idx] += delta;
+        self.ratios[neighbour] -= delta;
+    }
+
+    fn height_for(&self, idx: usize, output_height: i32) -> Size<i32, Logical> {
+        let mut height = (output_height as f32 * self.ratios[idx]).round() as i32;
+        // give any rounding remainder to the last window, so the column
+        // still sums to exactly `output_height`
+        if idx == self.windows.len() - 1 {
+            let allocated: i32 = self.ratios[..idx]
+                .iter()
+                .map(|r| (output_height as f32 * r).round() as i32)
+                .sum();
+            height = output_height - allocated;
+        }
+        Size::from((0, height.max(1)))
+    }
+}
+
+/// A scrollable-tiling layout: columns are arranged left-to-right on a
+/// horizontal strip that is conceptually infinite and private to the output
+/// it belongs to, so a column never overflows onto an adjacent monitor.
+#[derive(Debug, Default)]
+pub struct ScrollingLayout {
+    pub columns: Vec<Column>,
+    pub focused_column: usize,
+    /// Horizontal offset of the strip, in logical pixels, such that the
+    /// focused column is fully visible. Animated towards its target rather
+    /// than snapped.
+    pub view_offset: f64,
+    target_offset: f64,
+    /// Set while a scroll-animation timer is already ticking for this
+    /// workspace's strip, so repeated calls don't stack up duplicate timers.
+    animating: bool,
+    /// A window temporarily shown full-output instead of the strip, set by
+    /// an `xdg_toplevel::set_fullscreen` request. The strip resumes exactly
+    /// where it was once the window leaves fullscreen.
+    fullscreen: Option<Element>,
+}
+
+const GAP: i32 = 8;
+/// Fraction of the remaining distance closed per `advance_animation` tick.
+const ANIMATION_SPEED: f64 = 0.35;
+/// Roughly 60Hz; scroll animation steps are driven by a repeating timer
+/// rather than the output's real frame callback, which this layout has no
+/// access to.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+impl ScrollingLayout {
+    /// Inserts `window` into a new column to the right of the currently
+    /// focused one (or at the end, if there is no focus yet) and focuses it.
+    pub fn map_window(&mut self, workspace: &mut Workspace, window: Element) {
+        let insert_at = if self.columns.is_empty() {
+            0
+        } else {
+            self.focused_column + 1
+        };
+        self.columns.insert(insert_at, Column::new(window));
+        self.focused_column = insert_at;
+        self.arrange(workspace);
+        self.scroll_to_focused(workspace);
+    }
+
+    /// Removes `window` from whichever column holds it, collapsing the
+    /// column (and any resulting gap in the strip) if that was its last
+    /// window. Also drops it from the backing `Space` - leaving it mapped
+    /// there after it is gone from our own bookkeeping would mean it stays
+    /// visible on this output even once e.g. `Shell::move_window_to_output`
+    /// has re-mapped it elsewhere.
+    pub fn unmap_window(&mut self, workspace: &mut Workspace, window: &Element) {
+        let Some(col_idx) = self
+            .columns
+            .iter()
+            .position(|c| c.windows.iter().any(|w| w == window))
+        else {
+            return;
+        };
+
+        workspace.space.unmap_elem(window);
+        if self.fullscreen.as_ref() == Some(window) {
+            self.fullscreen = None;
+        }
+
+        let column = &mut self.columns[col_idx];
+        column.remove(window);
+        if column.windows.is_empty() {
+            self.columns.remove(col_idx);
+            if self.focused_column >= col_idx && self.focused_column > 0 {
+                self.focused_column -= 1;
+            }
+            self.focused_column = self.focused_column.min(self.columns.len().saturating_sub(1));
+        }
+
+        self.arrange(workspace);
+        self.scroll_to_focused(workspace);
+    }
+
+    /// Cycles the width preset of the focused column and re-arranges the
+    /// strip around it.
+    pub fn cycle_focused_width(&mut self, workspace: &mut Workspace) {
+        if let Some(column) = self.columns.get_mut(self.focused_column) {
+            column.width = column.width.next();
+            self.arrange(workspace);
+            self.scroll_to_focused(workspace);
+        }
+    }
+
+    /// Temporarily replaces the strip view with `window` alone, covering the
+    /// full output. The strip's columns keep their bookkeeping untouched and
+    /// resume exactly as they were on [`ScrollingLayout::unset_fullscreen`].
+    pub fn set_fullscreen(&mut self, workspace: &mut Workspace, window: Element) {
+        self.fullscreen = Some(window);
+        self.arrange(workspace);
+    }
+
+    /// Leaves fullscreen and restores the normal strip view.
+    pub fn unset_fullscreen(&mut self, workspace: &mut Workspace) {
+        if self.fullscreen.take().is_some() {
+            self.arrange(workspace);
+        }
+    }
+
+    /// Lays out every column left-to-right, maps their windows into the
+    /// backing space at the resulting geometry, and resizes each one (via a
+    /// real `xdg_toplevel` configure) to fill its column/row slot. Columns
+    /// further right than the output is wide are still placed - and thus
+    /// reachable by scrolling - just outside the immediately visible region.
+    fn arrange(&mut self, workspace: &mut Workspace) {
+        self.arrange_in(&mut workspace.space, &workspace.output);
+    }
+
+    fn arrange_in(&mut self, space: &mut Space<Element>, output: &smithay::output::Output) {
+        let output_geo = output
+            .current_mode()
+            .map(|mode| mode.size)
+            .unwrap_or_else(|| Size::from((0, 0)));
+        let output_width = output_geo.w;
+        let output_height = output_geo.h;
+
+        if let Some(window) = self.fullscreen.clone() {
+            space.map_element(window.clone(), Point::from((0, 0)), false);
+            configure_size(&window, Size::from((output_width, output_height)));
+            return;
+        }
+
+        let mut x = 0i32;
+        for column in &self.columns {
+            let width = ((output_width as f64 * column.width.fraction()) as i32).max(1);
+            let mut y = 0i32;
+            for (idx, window) in column.windows.iter().enumerate() {
+                let size = column.height_for(idx, output_height);
+                let location = Point::from((x - self.view_offset as i32, y));
+                space.map_element(window.clone(), location, false);
+                configure_size(window, Size::from((width, size.h)));
+                y += size.h;
+            }
+            x += width + GAP;
+        }
+    }
+
+    /// Picks a new target for `view_offset` such that the focused column is
+    /// fully on-screen. Only the target is set here; the scroll towards it is
+    /// stepped by the timer that [`ScrollingLayout::drive_animation`] starts.
+    fn scroll_to_focused(&mut self, workspace: &mut Workspace) {
+        let output_width = workspace
+            .output
+            .current_mode()
+            .map(|mode| mode.size.w)
+            .unwrap_or(0) as f64;
+
+        let mut x = 0f64;
+        let mut focused_width = 0f64;
+        for (idx, column) in self.columns.iter().enumerate() {
+            let width = output_width * column.width.fraction();
+            if idx == self.focused_column {
+                focused_width = width;
+                break;
+            }
+            x += width + GAP as f64;
+        }
+
+        self.target_offset = if x < self.view_offset {
+            x
+        } else if x + focused_width > self.view_offset + output_width {
+            x + focused_width - output_width
+        } else {
+            self.view_offset
+        };
+    }
+
+    /// Steps the scroll animation once. Returns whether the strip is still
+    /// moving, so the timer driving it knows whether to reschedule itself.
+    fn advance_animation(&mut self, space: &mut Space<Element>, output: &smithay::output::Output) -> bool {
+        let delta = self.target_offset - self.view_offset;
+        if delta.abs() < 0.5 {
+            self.view_offset = self.target_offset;
+            self.arrange_in(space, output);
+            return false;
+        }
+        self.view_offset += delta * ANIMATION_SPEED;
+        self.arrange_in(space, output);
+        true
+    }
+
+    /// Kicks off (if one isn't already running) a repeating timer that
+    /// steps the scroll animation once per frame until the strip comes to
+    /// rest at `target_offset`. Without this, `scroll_to_focused` only ever
+    /// picks a destination - nothing actually gets us there.
+    pub fn drive_animation(&mut self, handle: &LoopHandle<'static, crate::state::Data>, workspace: WorkspaceHandle) {
+        if self.animating || (self.target_offset - self.view_offset).abs() < 0.5 {
+            return;
+        }
+        self.animating = true;
+
+        let result = handle.insert_source(Timer::from_duration(FRAME_INTERVAL), move |_, _, data| {
+            let Some(ws) = data.state.common.shell.space_for_handle_mut(&workspace) else {
+                return TimeoutAction::Drop;
+            };
+            let Layout::Scrolling(scrolling) = &mut ws.layout else {
+                return TimeoutAction::Drop;
+            };
+            if scrolling.advance_animation(&mut ws.space, &ws.output) {
+                TimeoutAction::ToDuration(FRAME_INTERVAL)
+            } else {
+                scrolling.animating = false;
+                TimeoutAction::Drop
+            }
+        });
+        if result.is_err() {
+            self.animating = false;
+        }
+    }
+}
+
+/// Sends a real `xdg_toplevel` configure resizing `window` to `size`,
+/// skipping the round-trip if `size` is already the pending configure size.
+fn configure_size(window: &Element, size: Size<i32, Logical>) {
+    let Kind::Xdg(toplevel) = window.toplevel();
+    let already_sized = toplevel.with_pending_state(|state| state.size) == Some(size);
+    if already_sized {
+        return;
+    }
+    toplevel.with_pending_state(|state| state.size = Some(size));
+    toplevel.send_configure();
+}
+
+/// Active pointer-driven resize of a tiled window. Unlike floating's
+/// free-form resize, a horizontal-edge drag cycles the column's width preset
+/// in the direction dragged, while a vertical-edge drag adjusts this
+/// window's share of its column's height against its neighbour.
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeGrab {
+    pub edges: ResizeEdge,
+}
+
+/// Bookkeeping stashed on the window being resized, mirroring
+/// `floating::ResizeState`, so that later commits can tell how far the
+/// committed size has moved from where the grab started.
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeState {
+    pub edges: ResizeEdge,
+    pub initial_size: Size<i32, Logical>,
+}
+
+impl ResizeGrab {
+    /// Applies the size the client just committed, exactly like
+    /// `floating::ResizeSurfaceGrab::apply_resize_to_location`: a no-op
+    /// unless a [`ResizeState`] is stashed on `window` (i.e. a grab is
+    /// actually in progress), in which case a horizontal-edge resize cycles
+    /// the column's width preset in the direction the committed size moved,
+    /// and a vertical-edge resize nudges this window's share of its
+    /// column's height against the neighbour on the dragged side.
+    pub fn apply_resize_to_column(window: &Element, workspace: &mut Workspace, scrolling: &mut ScrollingLayout) {
+        let Some(state_cell) = window.user_data().get::<std::cell::RefCell<ResizeState>>() else {
+            return;
+        };
+        let Some(new_size) = window.toplevel().current_state().size else {
+            return;
+        };
+        let (edges, initial_size) = {
+            let state = state_cell.borrow();
+            (state.edges, state.initial_size)
+        };
+
+        let Some(col_idx) = scrolling
+            .columns
+            .iter()
+            .position(|c| c.windows.iter().any(|w| w == window))
+        else {
+            return;
+        };
+
+        if edges.intersects(ResizeEdge::LEFT | ResizeEdge::RIGHT) {
+            let delta_w = new_size.w - initial_size.w;
+            if delta_w != 0 {
+                let column = &mut scrolling.columns[col_idx];
+                column.width = if delta_w > 0 {
+                    column.width.next()
+                } else {
+                    column.width.prev()
+                };
+                scrolling.arrange(workspace);
+                scrolling.scroll_to_focused(workspace);
+            }
+        } else if edges.intersects(ResizeEdge::TOP | ResizeEdge::BOTTOM) {
+            let delta_h = new_size.h - initial_size.h;
+            if delta_h != 0 {
+                if let Some(win_idx) = scrolling.columns[col_idx]
+                    .windows
+                    .iter()
+                    .position(|w| w == window)
+                {
+                    scrolling.columns[col_idx].nudge_ratio(win_idx, edges, delta_h as f32 / HEIGHT_DRAG_STEP);
+                    scrolling.arrange(workspace);
+                }
+            }
+        }
+
+        state_cell.borrow_mut().initial_size = new_size;
+    }
+}
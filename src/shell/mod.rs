@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+
+use smithay::{
+    desktop::{space::SpaceElement, PopupManager, Space, Window},
+    input::Seat,
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point},
+};
+
+use crate::{
+    state::State,
+    wayland::protocols::screencopy::{BufferParams, Session as ScreencopySession},
+};
+
+pub mod layout;
+
+pub use self::layout::floating::FloatingLayout;
+pub use self::layout::scrolling::ScrollingLayout;
+
+/// The kind of element tracked by a [`Workspace`]. Aliased so the layout
+/// modules don't need to care whether we are still on top of smithay's
+/// `Window` type or have since grown a `CosmicMapped` wrapper around it.
+pub type Element = Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorkspaceHandle(pub(crate) usize);
+
+/// The layout in effect for a single workspace. Every workspace starts out
+/// floating and can be switched to scrolling-tiling independently of its
+/// neighbours.
+#[derive(Debug)]
+pub enum Layout {
+    Floating(FloatingLayout),
+    Scrolling(ScrollingLayout),
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Floating(FloatingLayout::default())
+    }
+}
+
+/// One virtual desktop, scoped to a single output. Holds the actual
+/// `smithay::desktop::Space` used for rendering/input hit-testing plus
+/// whichever layout is currently arranging it.
+#[derive(Debug)]
+pub struct Workspace {
+    pub handle: WorkspaceHandle,
+    pub output: Output,
+    pub space: Space<Element>,
+    pub layout: Layout,
+    pub pending_buffers: Vec<(ScreencopySession, BufferParams)>,
+}
+
+impl Workspace {
+    pub fn commit(&mut self, surface: &WlSurface) {
+        self.space.refresh();
+        let _ = surface;
+    }
+
+    pub fn element_for_surface(&self, surface: &WlSurface) -> Option<Element> {
+        self.space
+            .elements()
+            .find(|e| e.toplevel().wl_surface() == surface)
+            .cloned()
+    }
+}
+
+/// Per-output set of workspaces plus global compositor bookkeeping that
+/// doesn't belong to any one workspace (pending maps, popups, ...).
+///
+/// Invariant: each output owns a fully independent set of workspaces, and a
+/// window is owned by exactly one `(output, workspace)` pair at a time -
+/// never "visible" on two outputs' spaces ambiguously. The only exceptions
+/// are genuinely spanning cases (a fullscreen layer-shell surface, an
+/// output-mirroring screencopy session), and those are tracked explicitly in
+/// `explicit_spans` rather than falling out of an implicit global view.
+#[derive(Debug, Default)]
+pub struct Shell {
+    pub(crate) workspaces: HashMap<Output, Vec<Workspace>>,
+    pub(crate) active: HashMap<Output, usize>,
+    /// Outputs a surface is explicitly allowed to additionally render to, on
+    /// top of the single `(output, workspace)` pair that owns it.
+    pub(crate) explicit_spans: HashMap<WlSurface, Vec<Output>>,
+    pub pending_windows: Vec<(Element, Seat<State>)>,
+    pub pending_layers: Vec<(
+        smithay::desktop::LayerSurface,
+        Output,
+        smithay::wayland::shell::wlr_layer::Layer,
+    )>,
+    pub popups: PopupManager,
+}
+
+impl Shell {
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.workspaces.keys()
+    }
+
+    pub fn active_space(&self, output: &Output) -> &Workspace {
+        let idx = self.active.get(output).copied().unwrap_or(0);
+        &self.workspaces[output][idx]
+    }
+
+    pub fn active_space_mut(&mut self, output: &Output) -> &mut Workspace {
+        let idx = self.active.get(output).copied().unwrap_or(0);
+        self.workspaces.get_mut(output).unwrap().get_mut(idx).unwrap()
+    }
+
+    pub fn space_for_handle_mut(&mut self, handle: &WorkspaceHandle) -> Option<&mut Workspace> {
+        self.workspaces
+            .values_mut()
+            .flatten()
+            .find(|w| &w.handle == handle)
+    }
+
+    pub fn space_for_mut(&mut self, element: &Element) -> Option<&mut Workspace> {
+        self.workspaces
+            .values_mut()
+            .flatten()
+            .find(|w| w.space.elements().any(|e| e == element))
+    }
+
+    pub fn element_for_surface(&self, surface: &WlSurface) -> Option<Element> {
+        self.workspaces
+            .values()
+            .flatten()
+            .find_map(|w| w.element_for_surface(surface))
+    }
+
+    /// Places a newly mapped window into the output's active workspace,
+    /// handing it to whichever layout is currently active there.
+    ///
+    /// Enforces the one-owner invariant outright rather than merely
+    /// asserting it: if `window` is already mapped into some other
+    /// `(output, workspace)` pair, it is unmapped from there first, so it is
+    /// never briefly visible through two pairs at once - in release builds
+    /// too, not just behind `workspace_for_surface`'s `debug_assert!`.
+    pub fn map_window(state: &mut State, window: &Element, output: &Output) {
+        if state.common.shell.space_for_mut(window).is_some() {
+            Shell::unmap_window(state, window);
+        }
+
+        let handle = {
+            let workspace = state.common.shell.active_space_mut(output);
+            // take the layout out so we can pass `workspace` on to it by
+            // unique reference at the same time as matching on the variant
+            let mut layout = std::mem::take(&mut workspace.layout);
+            match &mut layout {
+                Layout::Floating(floating) => {
+                    let location = Point::from((0, 0));
+                    floating.map_window(workspace, window, location);
+                }
+                Layout::Scrolling(scrolling) => scrolling.map_window(workspace, window.clone()),
+            }
+            workspace.layout = layout;
+            workspace.handle
+        };
+        state.common.shell.drive_scroll_animation(&state.common.event_loop_handle, handle);
+    }
+
+    pub fn unmap_window(state: &mut State, window: &Element) {
+        let Some(handle) = state.common.shell.space_for_mut(window).map(|w| w.handle) else {
+            return;
+        };
+        {
+            let workspace = state.common.shell.space_for_handle_mut(&handle).unwrap();
+            let mut layout = std::mem::take(&mut workspace.layout);
+            match &mut layout {
+                Layout::Floating(floating) => floating.unmap_window(workspace, window),
+                Layout::Scrolling(scrolling) => scrolling.unmap_window(workspace, window),
+            }
+            workspace.layout = layout;
+        }
+        state.common.shell.drive_scroll_animation(&state.common.event_loop_handle, handle);
+    }
+
+    /// Starts the scroll-animation timer for `handle`'s workspace if it is a
+    /// [`Layout::Scrolling`] one with a pending target offset. A no-op for
+    /// floating workspaces or ones already at rest.
+    fn drive_scroll_animation(&mut self, handle: &smithay::reexports::calloop::LoopHandle<'static, crate::state::Data>, workspace: WorkspaceHandle) {
+        if let Some(ws) = self.space_for_handle_mut(&workspace) {
+            if let Layout::Scrolling(scrolling) = &mut ws.layout {
+                scrolling.drive_animation(handle, workspace);
+            }
+        }
+    }
+
+    /// Transfers ownership of `window` to `target`'s active workspace. This
+    /// is the only sanctioned way a window moves from one output's strip to
+    /// another's: the window is fully unmapped from its current
+    /// `(output, workspace)` and re-mapped (and so re-homed) on `target`,
+    /// rather than ever being considered "visible" on both at once.
+    /// `map_window` already enforces this on its own, so this is really just
+    /// `unmap` + `map` under a name that says what the pair means together.
+    ///
+    /// Note: this tree has no bezel-crossing pointer-drag code yet (no
+    /// `seat`/pointer-grab module at all) to call this from; it exists as
+    /// the prepared entry point for whenever that input path lands, not as
+    /// a currently-exercised one.
+    pub fn move_window_to_output(state: &mut State, window: &Element, target: &Output) {
+        Shell::unmap_window(state, window);
+        Shell::map_window(state, window, target);
+    }
+
+    /// Returns the single `(workspace, output)` pair that owns `surface`, if
+    /// it is currently mapped anywhere. Ownership is exclusive: `map_window`
+    /// unmaps a window from its previous pair before mapping it into a new
+    /// one, so a surface never belongs to more than one pair at a time in
+    /// any build; the `debug_assert!` below is a cheap extra check in debug
+    /// builds for bugs that would defeat that (e.g. a layout forgetting to
+    /// unmap from its `Space` on its own, bypassing `map_window` entirely),
+    /// panicking here instead of silently picking whichever one the
+    /// iteration order happened to hit first.
+    pub fn workspace_for_surface(&self, surface: &WlSurface) -> Option<(WorkspaceHandle, Output)> {
+        let mut matches = self.workspaces.iter().flat_map(|(output, spaces)| {
+            spaces
+                .iter()
+                .filter_map(move |w| w.element_for_surface(surface).map(|_| (w.handle, output.clone())))
+        });
+
+        let first = matches.next();
+        debug_assert!(
+            matches.next().is_none(),
+            "surface is owned by more than one (output, workspace) pair, violating Shell's one-owner invariant"
+        );
+        first
+    }
+
+    /// Same as [`Shell::workspace_for_surface`], wrapped in a `Vec` for call
+    /// sites that reconcile screencopy sessions across every owning pair;
+    /// for a tiled window this is always zero-or-one elements.
+    pub fn workspaces_for_surface(
+        &self,
+        surface: &WlSurface,
+    ) -> Vec<(WorkspaceHandle, Output)> {
+        self.workspace_for_surface(surface).into_iter().collect()
+    }
+
+    /// Returns every output `surface` should be composited onto: its owning
+    /// output, plus any output it has been explicitly registered to also
+    /// span (see `explicit_spans`). For a tiled window this is always
+    /// exactly the owning output, and never spans two physical outputs.
+    pub fn visible_outputs_for_surface(&self, surface: &WlSurface) -> Vec<Output> {
+        let mut outputs: Vec<Output> = self
+            .workspace_for_surface(surface)
+            .into_iter()
+            .map(|(_, output)| output)
+            .collect();
+
+        if let Some(extra) = self.explicit_spans.get(surface) {
+            for output in extra {
+                if !outputs.contains(output) {
+                    outputs.push(output.clone());
+                }
+            }
+        }
+
+        outputs
+    }
+
+    /// Explicitly marks `surface` as additionally visible on `outputs`, on
+    /// top of the single `(output, workspace)` pair that owns it. Meant for
+    /// the handful of genuinely spanning cases (a fullscreen layer-shell
+    /// surface, an output-mirroring screencopy session) that would otherwise
+    /// violate the one-owner invariant.
+    ///
+    /// Note: neither of those call sites exists in this tree yet (no
+    /// layer-shell fullscreen request handling, no mirroring screencopy
+    /// session type) - this is the invariant-safe extension point they are
+    /// meant to call into once they do, not a currently-exercised path.
+    pub fn set_explicit_span(&mut self, surface: WlSurface, outputs: Vec<Output>) {
+        if outputs.is_empty() {
+            self.explicit_spans.remove(&surface);
+        } else {
+            self.explicit_spans.insert(surface, outputs);
+        }
+    }
+}
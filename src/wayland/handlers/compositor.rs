@@ -12,7 +12,11 @@ use smithay::{
     backend::renderer::utils::{on_commit_buffer_handler, with_renderer_surface_state},
     delegate_compositor,
     desktop::{layer_map_for_output, Kind, LayerSurface, PopupKind, WindowSurfaceType},
-    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    output::Output,
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel::State as ToplevelState,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
     utils::IsAlive,
     wayland::{
         compositor::{with_states, CompositorHandler, CompositorState},
@@ -26,7 +30,8 @@ use smithay::{
 };
 use std::sync::Mutex;
 
-use super::screencopy::{self, PendingScreencopyBuffers};
+use super::screencopy::PendingScreencopyBuffers;
+use crate::shell::Layout;
 
 impl State {
     fn early_import_surface(&mut self, surface: &WlSurface) {
@@ -49,7 +54,7 @@ impl State {
         }
     }
 
-    fn toplevel_ensure_initial_configure(&mut self, toplevel: &ToplevelSurface) -> bool {
+    fn toplevel_ensure_initial_configure(&mut self, toplevel: &ToplevelSurface, output: &Output) -> bool {
         // send the initial configure if relevant
         let initial_configure_sent = with_states(toplevel.wl_surface(), |states| {
             states
@@ -61,8 +66,37 @@ impl State {
                 .initial_configure_sent
         });
         if !initial_configure_sent {
-            // TODO: query expected size from shell (without inserting and mapping)
-            toplevel.with_pending_state(|states| states.size = None);
+            // honor a maximized/fullscreen request the client made before we
+            // got to send a configure at all, so it doesn't come up at the
+            // wrong size and then jump once it acks our "real" configure
+            let (requests_maximized, requests_fullscreen) = toplevel.with_pending_state(|state| {
+                (
+                    state.states.contains(ToplevelState::Maximized),
+                    state.states.contains(ToplevelState::Fullscreen),
+                )
+            });
+
+            let usable_area = layer_map_for_output(output).non_exclusive_zone();
+            let output_size = output
+                .current_mode()
+                .map(|mode| mode.size.to_logical(output.current_scale().integer_scale()))
+                .unwrap_or_default();
+
+            toplevel.with_pending_state(|state| {
+                if requests_fullscreen {
+                    state.size = Some(output_size);
+                    state.states.set(ToplevelState::Fullscreen);
+                } else if requests_maximized {
+                    state.size = Some(usable_area.size);
+                    state.states.set(ToplevelState::Maximized);
+                } else {
+                    state.size = None;
+                }
+                // the largest size the client should pick if left
+                // unconstrained, so well-behaved clients size themselves
+                // correctly on the very first frame
+                state.bounds = Some(usable_area.size);
+            });
             toplevel.send_configure();
         }
         initial_configure_sent
@@ -126,12 +160,12 @@ impl CompositorHandler for State {
         {
             match window.toplevel() {
                 Kind::Xdg(toplevel) => {
-                    if self.toplevel_ensure_initial_configure(&toplevel)
+                    let output = seat.active_output();
+                    if self.toplevel_ensure_initial_configure(&toplevel, &output)
                         && with_renderer_surface_state(&surface, |state| {
                             state.wl_buffer().is_some()
                         })
                     {
-                        let output = seat.active_output();
                         Shell::map_window(self, &window, &output);
                     } else {
                         return;
@@ -168,34 +202,28 @@ impl CompositorHandler for State {
                     element.clone(),
                     workspace,
                 );
+
+                // same reconciliation, for whichever workspaces are tiled
+                // instead of floating; a no-op unless a scrolling resize
+                // grab is actually in progress on this window
+                let mut layout = std::mem::take(&mut workspace.layout);
+                if let Layout::Scrolling(scrolling) = &mut layout {
+                    crate::shell::layout::scrolling::ResizeGrab::apply_resize_to_column(
+                        &element, workspace, scrolling,
+                    );
+                }
+                workspace.layout = layout;
+
                 workspace.commit(surface);
             }
 
-            // handle window screencopy sessions
+            // handle window screencopy sessions; coalesced so a burst of
+            // commits (e.g. a video frame loop) only queues a single render
+            // per session/buffer per event-loop turn
             let active = element.active_window();
             if active.toplevel().wl_surface() == surface {
                 for (session, params) in active.pending_buffers() {
-                    let window = active.clone();
-                    self.common.event_loop_handle.insert_idle(move |data| {
-                        if !session.alive() {
-                            return;
-                        }
-
-                        match screencopy::render_window_to_buffer(
-                            &mut data.state,
-                            &session,
-                            params.clone(),
-                            &window,
-                        ) {
-                            // rendering yielded no damage, buffer is still pending
-                            Ok(false) => data.state.common.still_pending(session, params),
-                            Ok(true) => {} // success
-                            Err((reason, err)) => {
-                                slog_scope::warn!("Screencopy session failed: {}", err);
-                                session.failed(reason);
-                            }
-                        }
-                    });
+                    self.schedule_window_screencopy(session, params, &active);
                 }
             }
         }
@@ -242,31 +270,16 @@ impl CompositorHandler for State {
                                 .get_or_insert_with(Vec::new)
                                 .push((session, params));
                         } else if handle == w && output == o {
-                            // surface is visible on an offscreen workspace session, schedule a new render
+                            // surface is visible on an offscreen workspace session; schedule a
+                            // render, coalesced with any other pending render for this session
                             let (session, params) = workspace.pending_buffers.remove(i);
-                            let output = output.clone();
-                            self.common.event_loop_handle.insert_idle(move |data| {
-                                if !session.alive() {
-                                    return;
-                                }
-                                match screencopy::render_workspace_to_buffer(
-                                    &mut data.state,
-                                    &session,
-                                    params.clone(),
-                                    &output,
-                                    &handle,
-                                ) {
-                                    Ok(false) => {
-                                        // rendering yielded no new damage, buffer still pending
-                                        data.state.common.still_pending(session, params);
-                                    }
-                                    Ok(true) => {}
-                                    Err((reason, err)) => {
-                                        slog_scope::warn!("Screencopy session failed: {}", err);
-                                        session.failed(reason);
-                                    }
-                                }
-                            });
+                            self.schedule_workspace_screencopy(
+                                session,
+                                params,
+                                output.clone(),
+                                handle,
+                                surface,
+                            );
                         } else {
                             i += 1;
                         }
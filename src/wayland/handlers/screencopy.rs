@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use smithay::{
+    backend::renderer::{
+        buffer_dimensions,
+        damage::OutputDamageTracker,
+        element::{surface::render_elements_from_surface_tree, Kind as ElementKind, RenderElement},
+        gles::GlesRenderer,
+        utils::with_renderer_surface_state,
+        ExportMem, Renderer,
+    },
+    desktop::{space::space_render_elements, Window},
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Buffer as BufferCoord, IsAlive, Physical, Rectangle, Scale, Size, Transform},
+    wayland::shm::with_buffer_contents_mut,
+};
+
+use crate::{
+    shell::WorkspaceHandle,
+    state::{BackendData, State},
+    wayland::protocols::screencopy::{BufferParams, FailureReason, Session, SessionType},
+};
+
+/// Buffers a client has queued for a surface/workspace via `wlr-screencopy`
+/// but that haven't been rendered into yet, stashed in the relevant
+/// [`Output`]'s or window's userdata.
+#[derive(Debug, Default)]
+pub struct PendingScreencopyBuffers(pub RefCell<Vec<(Session, BufferParams)>>);
+
+pub trait UserdataExt {
+    fn pending_buffers(&self) -> Vec<(Session, BufferParams)>;
+}
+
+impl UserdataExt for Window {
+    fn pending_buffers(&self) -> Vec<(Session, BufferParams)> {
+        self.user_data()
+            .get::<PendingScreencopyBuffers>()
+            .map(|buffers| buffers.0.borrow_mut().drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Coalesces the idle-render closures `commit` would otherwise queue once
+/// per commit. Under a tight commit loop (e.g. a video frame source) this
+/// collapses a burst of `insert_idle` calls referring to the same session
+/// and buffer into a single pending render per event-loop turn, and tracks
+/// the surface damage accumulated across those collapsed commits so the
+/// eventual render only has to re-encode what actually changed.
+#[derive(Default)]
+pub struct RenderScheduler {
+    queued: HashSet<(Session, BufferParams)>,
+    damage: HashMap<Session, Vec<Rectangle<i32, Physical>>>,
+}
+
+impl RenderScheduler {
+    /// Accumulates `damage` for `session`, to be handed to the render call
+    /// once it actually runs.
+    fn accumulate_damage(&mut self, session: &Session, damage: impl IntoIterator<Item = Rectangle<i32, Physical>>) {
+        self.damage.entry(session.clone()).or_default().extend(damage);
+    }
+
+    /// Takes (and clears) the damage accumulated for `session` since its
+    /// last render. `None` if no entry is left, e.g. because a render of the
+    /// same session into another buffer already took it; the render then
+    /// falls back to a full repaint.
+    fn take_damage(&mut self, session: &Session) -> Option<Vec<Rectangle<i32, Physical>>> {
+        self.damage.remove(session)
+    }
+
+    /// Returns `true` (and marks the pair as queued) if no render for this
+    /// exact `(Session, BufferParams)` pair is already pending this turn.
+    /// The caller should only schedule an idle render when this returns
+    /// `true`.
+    fn try_reserve(&mut self, session: &Session, params: &BufferParams) -> bool {
+        self.queued.insert((session.clone(), params.clone()))
+    }
+
+    fn release(&mut self, session: &Session, params: &BufferParams) {
+        self.queued.remove(&(session.clone(), params.clone()));
+    }
+}
+
+impl State {
+    /// Schedules a window render for `session`/`params`, unless one is
+    /// already queued for this event-loop turn, in which case the commit
+    /// that triggered this call is folded into the damage of the pending
+    /// one.
+    pub fn schedule_window_screencopy(&mut self, session: Session, params: BufferParams, window: &Window) {
+        let surface = window.toplevel().wl_surface().clone();
+        let damage = surface_damage(&surface);
+        self.common
+            .screencopy_scheduler
+            .accumulate_damage(&session, damage);
+
+        if !self
+            .common
+            .screencopy_scheduler
+            .try_reserve(&session, &params)
+        {
+            return;
+        }
+
+        let window = window.clone();
+        self.common.event_loop_handle.insert_idle(move |data| {
+            data.state
+                .common
+                .screencopy_scheduler
+                .release(&session, &params);
+            if !session.alive() {
+                return;
+            }
+
+            let damage = data.state.common.screencopy_scheduler.take_damage(&session);
+            match render_window_to_buffer(&mut data.state, &session, params.clone(), &window, damage) {
+                Ok(false) => data.state.common.still_pending(session, params),
+                Ok(true) => {}
+                Err((reason, err)) => {
+                    slog_scope::warn!("Screencopy session failed: {}", err);
+                    session.failed(reason);
+                }
+            }
+        });
+    }
+
+    /// Same coalescing as [`State::schedule_window_screencopy`], but for a
+    /// whole-workspace capture. `surface` is whichever committed surface
+    /// triggered this particular schedule call, so its damage can be folded
+    /// in exactly like the per-window path does.
+    pub fn schedule_workspace_screencopy(
+        &mut self,
+        session: Session,
+        params: BufferParams,
+        output: Output,
+        handle: WorkspaceHandle,
+        surface: &WlSurface,
+    ) {
+        let damage = surface_damage(surface);
+        self.common
+            .screencopy_scheduler
+            .accumulate_damage(&session, damage);
+
+        if !self
+            .common
+            .screencopy_scheduler
+            .try_reserve(&session, &params)
+        {
+            return;
+        }
+
+        self.common.event_loop_handle.insert_idle(move |data| {
+            data.state
+                .common
+                .screencopy_scheduler
+                .release(&session, &params);
+            if !session.alive() {
+                return;
+            }
+
+            let damage = data.state.common.screencopy_scheduler.take_damage(&session);
+            match render_workspace_to_buffer(&mut data.state, &session, params.clone(), &output, &handle, damage) {
+                Ok(false) => data.state.common.still_pending(session, params),
+                Ok(true) => {}
+                Err((reason, err)) => {
+                    slog_scope::warn!("Screencopy session failed: {}", err);
+                    session.failed(reason);
+                }
+            }
+        });
+    }
+}
+
+fn surface_damage(surface: &WlSurface) -> Vec<Rectangle<i32, Physical>> {
+    with_renderer_surface_state(surface, |state| state.damage().to_vec()).unwrap_or_default()
+}
+
+/// Merges `damage` into the single region that needs re-encoding, clamped to
+/// both the destination buffer's own dimensions and the sub-region of it the
+/// client actually asked for (`requested`). Falls back to repainting
+/// everything within that clamp if there is no damage to go on (`damage` is
+/// `None`). Returns `None` if there is nothing to (re-)encode, either
+/// because no commit actually damaged anything yet, or because the damage
+/// that did happen falls entirely outside the buffer/requested bounds.
+fn compute_copy_region(
+    damage: &Option<Vec<Rectangle<i32, Physical>>>,
+    buffer: &smithay::reexports::wayland_server::protocol::wl_buffer::WlBuffer,
+    requested: Rectangle<i32, BufferCoord>,
+) -> Option<Rectangle<i32, Physical>> {
+    let buffer_size = buffer_dimensions(buffer).unwrap_or_default();
+    let buffer_bounds = Rectangle::from_loc_and_size((0, 0), buffer_size);
+    let requested = Rectangle::from_loc_and_size(
+        (requested.loc.x, requested.loc.y),
+        (requested.size.w, requested.size.h),
+    );
+    let capture_bounds = buffer_bounds.intersection(requested)?;
+
+    let damaged = match damage {
+        // commits happened, but none of them actually damaged anything -
+        // still pending, nothing to (re-)encode
+        Some(rects) if rects.is_empty() => return None,
+        Some(rects) => rects
+            .iter()
+            .skip(1)
+            .fold(rects[0], |acc, rect| acc.merge(*rect)),
+        None => capture_bounds,
+    };
+
+    damaged.intersection(capture_bounds)
+}
+
+/// Renders `window` into the buffer backing `session`/`params`. When
+/// `damage` is `Some` and non-empty, only the bounding box of it is
+/// re-encoded; that region is not sent to the client as damage. When it is
+/// `Some` but empty, nothing is rendered and `Ok(false)` is returned. When it
+/// is `None`, the whole buffer is repainted.
+pub fn render_window_to_buffer(
+    state: &mut State,
+    session: &Session,
+    params: BufferParams,
+    window: &Window,
+    damage: Option<Vec<Rectangle<i32, Physical>>>,
+) -> Result<bool, (FailureReason, anyhow::Error)> {
+    let Some(region) = compute_copy_region(&damage, &params.buffer, params.region) else {
+        return Ok(false);
+    };
+    let full_size = buffer_dimensions(&params.buffer).unwrap_or_default();
+
+    let output = match session.session_type() {
+        SessionType::Output(output) | SessionType::Workspace(output, _) => output,
+    };
+    let scale = Scale::from(output.current_scale().fractional_scale());
+
+    let renderer = renderer_for_output(state, &output)
+        .ok_or_else(|| (FailureReason::Unknown, anyhow::anyhow!("no renderer available for output")))?;
+    let elements = render_elements_from_surface_tree(
+        renderer,
+        window.toplevel().wl_surface(),
+        (0, 0),
+        scale,
+        1.0,
+        ElementKind::Unspecified,
+    );
+
+    copy_elements_to_buffer(state, &output, &params, &elements, full_size, region)?;
+    Ok(true)
+}
+
+/// Renders every window on the workspace identified by `(output, handle)`
+/// into the buffer backing `session`/`params`, with the same damage
+/// semantics as [`render_window_to_buffer`].
+pub fn render_workspace_to_buffer(
+    state: &mut State,
+    session: &Session,
+    params: BufferParams,
+    output: &Output,
+    handle: &WorkspaceHandle,
+    damage: Option<Vec<Rectangle<i32, Physical>>>,
+) -> Result<bool, (FailureReason, anyhow::Error)> {
+    let _ = session;
+    let Some(region) = compute_copy_region(&damage, &params.buffer, params.region) else {
+        return Ok(false);
+    };
+    let full_size = buffer_dimensions(&params.buffer).unwrap_or_default();
+
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    let workspace = state
+        .common
+        .shell
+        .space_for_handle_mut(handle)
+        .ok_or_else(|| (FailureReason::Unknown, anyhow::anyhow!("workspace no longer exists")))?;
+    let elements = space_render_elements::<_, _, GlesRenderer>(&workspace.space, scale)
+        .map_err(|err| (FailureReason::Unknown, anyhow::anyhow!("{err:?}")))?;
+
+    copy_elements_to_buffer(state, output, &params, &elements, full_size, region)?;
+    Ok(true)
+}
+
+/// Gets at the renderer for `output`'s GPU, mirroring the lookup
+/// `early_import_surface` already does for import targets.
+fn renderer_for_output<'a>(state: &'a mut State, output: &Output) -> Option<&'a mut GlesRenderer> {
+    match &mut state.backend {
+        BackendData::Kms(kms) => kms.renderer_for_output(output),
+    }
+}
+
+/// Renders `elements` - positioned at their real output/window-local
+/// coordinates - into a target sized to the full `full_size`, then copies
+/// just `region` of that result into the shm buffer backing `params`, so
+/// only the damaged (and buffer/client-requested-region-clamped) rectangle
+/// is ever re-encoded instead of the whole frame. `region` must already be
+/// contained within `full_size`; `compute_copy_region` guarantees this.
+fn copy_elements_to_buffer<E>(
+    state: &mut State,
+    output: &Output,
+    params: &BufferParams,
+    elements: &[E],
+    full_size: Size<i32, Physical>,
+    region: Rectangle<i32, Physical>,
+) -> Result<(), (FailureReason, anyhow::Error)>
+where
+    E: RenderElement<GlesRenderer>,
+{
+    let renderer = renderer_for_output(state, output)
+        .ok_or_else(|| (FailureReason::Unknown, anyhow::anyhow!("no renderer available for output")))?;
+
+    // the tracker/render target always covers the whole output or window,
+    // matching the coordinate space `elements` are already positioned in -
+    // only the readback below is restricted to `region`
+    let mut damage_tracker = OutputDamageTracker::new(full_size, 1.0, Transform::Normal);
+    damage_tracker
+        .render_output(renderer, 0, elements, [0.0, 0.0, 0.0, 1.0])
+        .map_err(|err| (FailureReason::Unknown, anyhow::anyhow!("{err:?}")))?;
+
+    let mapping = renderer
+        .copy_framebuffer(region, smithay::backend::allocator::Fourcc::Argb8888)
+        .map_err(|err| (FailureReason::Unknown, anyhow::anyhow!("{err:?}")))?;
+    let pixels = renderer
+        .map_texture(&mapping)
+        .map_err(|err| (FailureReason::Unknown, anyhow::anyhow!("{err:?}")))?;
+
+    let row_bytes = region.size.w as usize * 4;
+    with_buffer_contents_mut(&params.buffer, |ptr, _len, data| {
+        let stride = data.stride as usize;
+        let dst_origin = (region.loc.y as usize) * stride + (region.loc.x as usize) * 4;
+        for row in 0..region.size.h as usize {
+            let src = &pixels[row * row_bytes..(row + 1) * row_bytes];
+            let dst = dst_origin + row * stride;
+            // SAFETY: `dst + row_bytes` stays within `stride * height` as
+            // long as `region` was clamped to the buffer's own dimensions,
+            // which `compute_copy_region` guarantees.
+            unsafe {
+                std::ptr::copy_nonoverlapping(src.as_ptr(), ptr.add(dst), row_bytes);
+            }
+        }
+    })
+    .map_err(|err| (FailureReason::BufferConstraints, anyhow::anyhow!("{err}")))?;
+
+    Ok(())
+}
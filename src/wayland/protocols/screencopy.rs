@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! `wlr-screencopy-unstable-v1` session and buffer-request bookkeeping.
+
+use std::{
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use smithay::{
+    output::Output,
+    reexports::wayland_server::protocol::wl_buffer::WlBuffer,
+    utils::{Buffer as BufferCoord, Rectangle},
+};
+
+use crate::shell::WorkspaceHandle;
+
+/// What a [`Session`] is capturing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SessionType {
+    Output(Output),
+    Workspace(Output, WorkspaceHandle),
+}
+
+/// Why a screencopy frame could not be produced. [`Session::failed`] only
+/// marks the session as no longer alive; the reason is not stored or sent.
+#[derive(Debug, Clone, Copy)]
+pub enum FailureReason {
+    Unknown,
+    BufferConstraints,
+}
+
+#[derive(Debug)]
+struct SessionInner {
+    session_type: SessionType,
+    alive: AtomicBool,
+}
+
+/// A live `zwlr_screencopy_frame_v1` capture request. Cheap to clone; every
+/// clone refers to the same underlying request, so they compare and hash as
+/// the same session (used as a scheduling key in the render coalescer).
+#[derive(Debug, Clone)]
+pub struct Session(Arc<SessionInner>);
+
+impl Session {
+    pub fn new(session_type: SessionType) -> Self {
+        Session(Arc::new(SessionInner {
+            session_type,
+            alive: AtomicBool::new(true),
+        }))
+    }
+
+    pub fn session_type(&self) -> SessionType {
+        self.0.session_type.clone()
+    }
+
+    pub fn alive(&self) -> bool {
+        self.0.alive.load(Ordering::Acquire)
+    }
+
+    pub fn failed(&self, reason: FailureReason) {
+        let _ = reason;
+        self.0.alive.store(false, Ordering::Release);
+    }
+}
+
+impl PartialEq for Session {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+impl Eq for Session {}
+impl Hash for Session {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const () as usize).hash(state);
+    }
+}
+
+/// The client-provided buffer a [`Session`]'s next frame should be rendered
+/// into, plus the region of it actually requested.
+#[derive(Debug, Clone)]
+pub struct BufferParams {
+    pub buffer: WlBuffer,
+    pub region: Rectangle<i32, BufferCoord>,
+}
+
+impl PartialEq for BufferParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.buffer == other.buffer && self.region == other.region
+    }
+}
+impl Eq for BufferParams {}
+impl Hash for BufferParams {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        use smithay::reexports::wayland_server::Resource;
+        self.buffer.id().hash(state);
+    }
+}